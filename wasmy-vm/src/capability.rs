@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use lazy_static;
+use rand::random;
+
+use crate::WasmUri;
+
+lazy_static::lazy_static! {
+    static ref GRANTS: RwLock<HashMap<WasmUri, Permissions>> = RwLock::new(HashMap::new());
+}
+
+/// A bitset of granted host method ids, one bit per id, growing a 64-bit
+/// word at a time as higher ids are granted -- a fixed single `u64` would
+/// alias ids that are 64 apart onto the same bit and leak grants across methods.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Permissions(Vec<u64>);
+
+impl Permissions {
+    pub const NONE: Permissions = Permissions(Vec::new());
+
+    pub fn grant(mut self, method_id: u32) -> Self {
+        let word = (method_id / 64) as usize;
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1u64 << (method_id % 64);
+        self
+    }
+
+    pub(crate) fn allows(&self, method_id: u32) -> bool {
+        let word = (method_id / 64) as usize;
+        self.0.get(word).map_or(false, |bits| bits & (1u64 << (method_id % 64)) != 0)
+    }
+}
+
+/// A capability minted for one local `Instance`. Every `_vm_invoke` checks
+/// the calling instance's own `perms` before dispatching. The guest does not
+/// present the handle; the random `id` only appears in denial logs and errors.
+#[derive(Clone, Debug)]
+pub(crate) struct Handle {
+    pub(crate) id: u64,
+    pub(crate) perms: Permissions,
+}
+
+impl Handle {
+    pub(crate) fn mint(perms: Permissions) -> Self {
+        Handle { id: random(), perms }
+    }
+}
+
+/// Records the permissions to grant every local instance of `wasm_uri`.
+pub(crate) fn set(wasm_uri: WasmUri, perms: Permissions) {
+    GRANTS.write().unwrap().insert(wasm_uri, perms);
+}
+
+/// Looks up the permissions granted to `wasm_uri`, defaulting to
+/// [`Permissions::NONE`] if none were granted at load time.
+pub(crate) fn get(wasm_uri: &WasmUri) -> Permissions {
+    GRANTS.read().unwrap().get(wasm_uri).cloned().unwrap_or_default()
+}
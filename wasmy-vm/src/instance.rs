@@ -2,7 +2,7 @@ use core::ops::FnOnce;
 use std::alloc::{alloc, Layout};
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::sync::{Mutex, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::thread::ThreadId;
 
@@ -12,7 +12,15 @@ use wasmer::{Function, import_namespace, ImportObject, Memory, MemoryView, Modul
 use wasmer_wasi::WasiState;
 
 use crate::{modules, WasmUri};
+use crate::capability;
+use crate::capability::{Handle, Permissions};
 use crate::handler::*;
+use crate::limits;
+use crate::limits::Limits;
+use crate::linker;
+use crate::linker::HostLinker;
+use crate::reactor::{Completion, PendingCall, Reactor, WakeToken};
+use crate::snapshot;
 use crate::wasm_file::WasmFile;
 
 lazy_static::lazy_static! {
@@ -20,7 +28,7 @@ lazy_static::lazy_static! {
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
-struct LocalInstanceKey {
+pub(crate) struct LocalInstanceKey {
     wasm_uri: WasmUri,
     thread_id: ThreadId,
 }
@@ -29,6 +37,9 @@ impl LocalInstanceKey {
     fn from(wasm_uri: WasmUri) -> LocalInstanceKey {
         LocalInstanceKey { wasm_uri, thread_id: thread::current().id() }
     }
+    pub(crate) fn wasm_uri(&self) -> &WasmUri {
+        &self.wasm_uri
+    }
 }
 
 #[derive(Clone, WasmerEnv, Debug)]
@@ -66,7 +77,60 @@ pub(crate) fn load<B, W>(wasm_file: W) -> Result<WasmUri>
     where B: AsRef<[u8]>,
           W: WasmFile<B>,
 {
-    let ins = Instance::load_and_new_local(wasm_file)?;
+    load_with_options(wasm_file, LoadOptions::default())
+}
+
+/// Resource limits, host-method grants and host function table applied to
+/// every local instance created for a `wasm_uri` loaded with
+/// [`load_with_options`].
+#[derive(Clone, Default)]
+pub(crate) struct LoadOptions {
+    limits: Limits,
+    perms: Permissions,
+    linker: Option<Arc<HostLinker>>,
+    clone_from_snapshot: bool,
+}
+
+impl LoadOptions {
+    pub(crate) fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Grants the loaded module's instances permission to call host method
+    /// `method_id` through `ctx.call_host`.
+    pub(crate) fn with_granted_method(mut self, method_id: u32) -> Self {
+        self.perms = self.perms.grant(method_id);
+        self
+    }
+
+    /// Installs the host function table guests resolve `(namespace, name)`
+    /// method ids against, replacing the embedder's single `vm_invoke` match
+    /// arm with composable per-crate registrations.
+    pub(crate) fn with_linker(mut self, linker: HostLinker) -> Self {
+        self.linker = Some(Arc::new(linker));
+        self
+    }
+
+    /// Lets every local instance of this module after the first be cloned
+    /// from a shared memory snapshot instead of independently running
+    /// `init()`. Only safe for modules whose onload doesn't read or store
+    /// thread-specific host state (e.g. the current `ThreadId`) -- since the
+    /// clone shares one thread's memory verbatim, any such state would
+    /// silently leak into every other thread's instance. Off by default.
+    pub(crate) fn with_snapshot_cloning(mut self) -> Self {
+        self.clone_from_snapshot = true;
+        self
+    }
+}
+
+/// Like [`load`], but applies `options` (memory/fuel limits and host-method
+/// grants) to every local instance created for this `wasm_uri` from now on.
+pub(crate) fn load_with_options<B, W>(wasm_file: W, options: LoadOptions) -> Result<WasmUri>
+    where B: AsRef<[u8]>,
+          W: WasmFile<B>,
+{
+    let ins = Instance::load_and_new_local(wasm_file, options)?;
     Ok(ins.key.wasm_uri.clone())
 }
 
@@ -89,17 +153,37 @@ pub(crate) struct Instance {
     instance: wasmer::Instance,
     loaded: Cell<bool>,
     context: RefCell<Context>,
+    limits: Limits,
+    /// Remaining fuel; `None` means unmetered (see `Limits::fuel`).
+    fuel_remaining: Cell<Option<u64>>,
+    handle: Handle,
+    /// Set by `_vm_invoke` when the guest's handle lacks permission for the
+    /// requested method id; taken by `invoke_instance` to abort the call.
+    perm_denied: Cell<Option<u32>>,
+    linker: Arc<HostLinker>,
+    /// Callback for whichever of `call_wasm_handler`/`resume_pending` is
+    /// currently re-entering the guest; `_vm_yield` takes it when parking so
+    /// the `Reactor` can deliver the eventual result to the original caller
+    /// instead of discarding it once the call resumes on another thread/tick.
+    pending_completion: RefCell<Option<Completion>>,
 }
 
+/// `ctx_bytes` and `swap_memory` are each grown on their own high-water mark
+/// and reused across calls rather than reallocated per call; they're kept as
+/// two separate buffers (not one shared arena) since both are alive at once
+/// during a call (ctx and args are recalled at different offsets).
 #[derive(Clone, Debug)]
 pub(crate) struct Context {
     ctx_bytes: Vec<u8>,
     swap_memory: Vec<u8>,
+    /// Set by `_vm_yield` once it has parked the call with the `Reactor`;
+    /// taken by `invoke_instance` to tell a yield apart from a completed call.
+    pending: Option<WakeToken>,
 }
 
 impl Context {
     fn with_capacity(capacity: usize) -> Self {
-        Self { ctx_bytes: Vec::with_capacity(capacity), swap_memory: Vec::with_capacity(capacity) }
+        Self { ctx_bytes: Vec::with_capacity(capacity), swap_memory: Vec::with_capacity(capacity), pending: None }
     }
 
     fn set_args<M: Message>(&mut self, ctx: Option<M>, in_args: InArgs) -> (usize, usize) {
@@ -128,6 +212,21 @@ impl Context {
     }
 }
 
+/// Result of re-entering a guest's exported symbol.
+pub(crate) enum CallOutcome {
+    /// The call ran to completion.
+    Done,
+    /// The guest parked itself via `_vm_yield`; `token` resumes it.
+    Yielded(WakeToken),
+}
+
+/// Result of `call_wasm_handler`, surfaced to the embedder instead of
+/// blocking until a parked guest call completes.
+pub(crate) enum HandlerOutcome {
+    Ready(OutRets),
+    Pending(WakeToken),
+}
+
 unsafe impl Sync for Instance {}
 
 unsafe impl Send for Instance {}
@@ -135,24 +234,35 @@ unsafe impl Send for Instance {}
 impl Instance {
     fn new_local(key: LocalInstanceKey) -> anyhow::Result<InstanceEnv> {
         if let Some(module) = modules::MODULES.read().unwrap().get(&key.wasm_uri) {
-            Self::from_module(module, key)
+            let limits = limits::get(&key.wasm_uri);
+            let perms = capability::get(&key.wasm_uri);
+            let host_linker = linker::get(&key.wasm_uri);
+            Self::from_module(module, key, limits, perms, host_linker)
         } else {
             Err(anyhow!("not found module"))
         }
     }
 
-    fn load_and_new_local<B, W>(wasm_file: W) -> anyhow::Result<InstanceEnv>
+    fn load_and_new_local<B, W>(wasm_file: W, options: LoadOptions) -> anyhow::Result<InstanceEnv>
         where B: AsRef<[u8]>,
               W: WasmFile<B>,
     {
         let wasm_uri = modules::load(wasm_file)?;
+        limits::set(wasm_uri.clone(), options.limits);
+        capability::set(wasm_uri.clone(), options.perms.clone());
+        snapshot::set_cloning_enabled(wasm_uri.clone(), options.clone_from_snapshot);
+        let host_linker = options.linker.unwrap_or_default();
+        linker::set(wasm_uri.clone(), host_linker.clone());
         Self::from_module(
             modules::MODULES.read().unwrap().get(&wasm_uri).as_ref().unwrap(),
             LocalInstanceKey::from(wasm_uri),
+            options.limits,
+            options.perms,
+            host_linker,
         )
     }
 
-    fn from_module(module: &Module, key: LocalInstanceKey) -> anyhow::Result<InstanceEnv> {
+    fn from_module(module: &Module, key: LocalInstanceKey, limits: Limits, perms: Permissions, host_linker: Arc<HostLinker>) -> anyhow::Result<InstanceEnv> {
         let ins_env = InstanceEnv::from(key);
 
         let import_object = Self::new_import_object(&module, &ins_env)?;
@@ -162,15 +272,37 @@ impl Instance {
             instance: wasmer::Instance::new(&module, &import_object)?,
             loaded: Cell::new(false),
             context: RefCell::new(Context::with_capacity(1024)),
+            limits,
+            fuel_remaining: Cell::new(limits.fuel),
+            handle: Handle::mint(perms),
+            perm_denied: Cell::new(None),
+            linker: host_linker,
+            pending_completion: RefCell::new(None),
         };
         #[cfg(debug_assertions)]println!("[{:?}] created instance: wasm_uri={}", ins_env.key.thread_id, ins_env.key.wasm_uri);
 
         ins_env.init_instance(instance);
 
-        // only call once
-        if let Err(e) = ins_env.as_instance().init() {
-            INSTANCES.write().unwrap().remove(&ins_env.key);
-            return Err(e);
+        let wasm_uri = ins_env.key.wasm_uri.clone();
+        let cloning_enabled = snapshot::cloning_enabled(&wasm_uri);
+        if let Some(snapshot) = snapshot::get(&wasm_uri).filter(|_| cloning_enabled) {
+            // A prior instance of this wasm_uri already ran onload and the
+            // module opted into `LoadOptions::with_snapshot_cloning`; clone
+            // its warmed-up memory instead of paying init() again.
+            ins_env.as_instance().restore_from_snapshot(&snapshot);
+            ins_env.as_instance().loaded.set(true);
+            #[cfg(debug_assertions)]println!("[{:?}] restored instance from snapshot: wasm_uri={}", ins_env.key.thread_id, wasm_uri);
+        } else {
+            // only call once
+            if let Err(e) = ins_env.as_instance().init() {
+                INSTANCES.write().unwrap().remove(&ins_env.key);
+                return Err(e);
+            }
+            if cloning_enabled {
+                if let Err(e) = snapshot::capture_if_absent(wasm_uri, &ins_env.as_instance().dump_memory()) {
+                    eprintln!("failed to capture memory snapshot: {}", e);
+                }
+            }
         }
         return Ok(ins_env)
     }
@@ -205,9 +337,78 @@ impl Instance {
                 let ins = ins_env.as_instance();
                 ins.use_ctx_swap_memory(size as usize, |buffer| {
                     ins.read_view_bytes(offset as usize, size as usize, buffer);
-                    write_to_vec(&vm_invoke(buffer), buffer)
+                    match InArgs::parse_from_bytes(buffer).ok().map(|a| a.get_method()) {
+                        Some(method_id) if !ins.handle.perms.allows(method_id) => {
+                            #[cfg(debug_assertions)] println!("[VM:{:?}]_vm_invoke denied: wasm_uri={}, handle={}, method_id={}", key.thread_id, key.wasm_uri, ins.handle.id, method_id);
+                            ins.perm_denied.set(Some(method_id));
+                            unsafe { buffer.set_len(0) };
+                            0
+                        }
+                        // A module loaded with a plain `load()` (no `with_linker`)
+                        // has an empty `HostLinker`; fall back to the legacy
+                        // `vm_invoke` dispatch instead of silently returning an
+                        // empty payload for every method id.
+                        Some(_) if ins.linker.is_empty() => {
+                            let result = vm_invoke(buffer);
+                            write_to_vec(&result, buffer)
+                        }
+                        Some(method_id) => {
+                            let result = ins.linker.dispatch(method_id, buffer);
+                            let len = result.len();
+                            resize_with_capacity(buffer, len);
+                            buffer[..len].copy_from_slice(&result);
+                            len
+                        }
+                        None => {
+                            #[cfg(debug_assertions)] println!("[VM:{:?}]_vm_invoke: wasm_uri={}, malformed args", key.thread_id, key.wasm_uri);
+                            unsafe { buffer.set_len(0) };
+                            0
+                        }
+                    }
                 }) as i32
             }),
+            "_vm_yield" => Function::new_native_with_env(module.store(), ins_env.clone(), |ins_env: &InstanceEnv, sign_name_offset: i32, sign_name_size: i32, ctx_size: i32, args_size: i32| -> i64 {
+                let key = &ins_env.key;
+                #[cfg(debug_assertions)] println!("[VM:{:?}]_vm_yield: wasm_uri={}, ctx_size={}, args_size={}", key.thread_id, key.wasm_uri, ctx_size, args_size);
+                let ins = ins_env.as_instance();
+                let mut sign_name = Vec::new();
+                ins.read_view_bytes(sign_name_offset as usize, sign_name_size as usize, &mut sign_name);
+                let sign_name = String::from_utf8_lossy(&sign_name).into_owned();
+                let call = PendingCall { sign_name, ctx_size, args_size };
+                let completion = ins.pending_completion.borrow_mut().take();
+                let token = Reactor::global().park(key.clone(), call, completion);
+                ins.context.borrow_mut().pending = Some(token);
+                token.0 as i64
+            }),
+            "_vm_wake" => Function::new_native_with_env(module.store(), ins_env.clone(), |_ins_env: &InstanceEnv, token: i64| {
+                #[cfg(debug_assertions)] println!("[VM]_vm_wake: token={}", token);
+                Reactor::global().wake(WakeToken(token as u64));
+            }),
+            "_vm_consume_fuel" => Function::new_native_with_env(module.store(), ins_env.clone(), |ins_env: &InstanceEnv, units: i32| -> std::result::Result<(), wasmer::RuntimeError> {
+                let ins = ins_env.as_instance();
+                // Trap here instead of only flagging exhaustion for
+                // `invoke_instance` to notice on return: a guest that never
+                // returns to the host (a tight compute loop spending fuel on
+                // every iteration) would otherwise run to completion before
+                // the host got a chance to stop it.
+                if ins.consume_fuel(units.max(0) as u64) {
+                    return Err(wasmer::RuntimeError::new(format!("out of fuel: wasm_uri={}", ins.key.wasm_uri)));
+                }
+                Ok(())
+            }),
+            "_vm_resolve" => Function::new_native_with_env(module.store(), ins_env.clone(), |ins_env: &InstanceEnv, namespace_offset: i32, namespace_size: i32, name_offset: i32, name_size: i32| -> i32 {
+                let key = &ins_env.key;
+                let ins = ins_env.as_instance();
+                let mut namespace = Vec::new();
+                ins.read_view_bytes(namespace_offset as usize, namespace_size as usize, &mut namespace);
+                let mut name = Vec::new();
+                ins.read_view_bytes(name_offset as usize, name_size as usize, &mut name);
+                let namespace = String::from_utf8_lossy(&namespace);
+                let name = String::from_utf8_lossy(&name);
+                let method_id = ins.linker.resolve(&namespace, &name);
+                #[cfg(debug_assertions)] println!("[VM:{:?}]_vm_resolve: wasm_uri={}, {}::{} -> {:?}", key.thread_id, key.wasm_uri, namespace, name, method_id);
+                method_id.map(|id| id as i32).unwrap_or(-1)
+            }),
         }));
 
         Ok(import_object)
@@ -235,18 +436,40 @@ impl Instance {
             ERR_CODE_NONE.to_result("instance has not completed initialization")
         }
     }
+    /// `on_yield_complete` is only ever invoked if this call parks via
+    /// `_vm_yield`: the `Reactor` hands it the eventual `Done`/error outcome
+    /// once the call resumes (possibly after yielding again any number of
+    /// times). A call that runs straight through ignores it and its result
+    /// comes back as `HandlerOutcome::Ready` below instead.
     #[inline]
-    pub(crate) fn call_wasm_handler<C: Message>(&self, ctx: Option<C>, method: Method, in_args: InArgs) -> Result<OutRets> {
+    pub(crate) fn call_wasm_handler<C: Message, F>(&self, ctx: Option<C>, method: Method, in_args: InArgs, on_yield_complete: F) -> Result<HandlerOutcome>
+        where F: FnOnce(Result<OutRets>) + Send + 'static,
+    {
         self.check_loaded()?;
         #[cfg(debug_assertions)] println!("method={}, data={:?}", in_args.get_method(), in_args.get_data());
+        // `Limits::fuel` is a per-call budget, not a lifetime total: reset it
+        // here so a call that used up its fuel doesn't brick every call after
+        // it. A resumed (yielded) call doesn't go through this path, so it
+        // keeps spending from whatever fuel it had left when it parked.
+        self.fuel_remaining.set(self.limits.fuel);
         let (ctx_size, args_size) = self.context.borrow_mut().set_args(ctx, in_args);
         let sign_name = WasmHandlerApi::method_to_symbol(method);
-        self.invoke_instance(&sign_name, Some((ctx_size as i32, args_size as i32)))?;
-        Ok(self.context.borrow_mut().out_rets())
+        *self.pending_completion.borrow_mut() = Some(Box::new(on_yield_complete));
+        match self.invoke_instance(&sign_name, Some((ctx_size as i32, args_size as i32)))? {
+            CallOutcome::Done => {
+                self.pending_completion.borrow_mut().take();
+                let rets = self.context.borrow_mut().out_rets();
+                Ok(HandlerOutcome::Ready(rets))
+            }
+            CallOutcome::Yielded(token) => Ok(HandlerOutcome::Pending(token)),
+        }
     }
-    pub(crate) fn invoke_instance(&self, sign_name: &str, args: Option<(i32, i32)>) -> Result<()> {
+    pub(crate) fn invoke_instance(&self, sign_name: &str, args: Option<(i32, i32)>) -> Result<CallOutcome> {
         let exports = &self.instance.exports;
         loop {
+            if self.fuel_exhausted() {
+                return ERR_CODE_FUEL.to_result(format!("out of fuel: wasm_uri={}, sign_name={}", self.key.wasm_uri, sign_name))
+            }
             let ret = if let Some((ctx_size, args_size)) = args.clone() {
                 exports
                     .get_native_function::<(i32, i32), ()>(sign_name)
@@ -259,9 +482,25 @@ impl Instance {
                     .call()
             };
             if let Err(e) = ret {
+                // `_vm_consume_fuel` traps the instant fuel hits zero, so a
+                // call can fail here with fuel already exhausted; report it
+                // as such instead of falling through to the OOM/generic path.
+                if self.fuel_exhausted() {
+                    return ERR_CODE_FUEL.to_result(format!("out of fuel: wasm_uri={}, sign_name={}", self.key.wasm_uri, sign_name))
+                }
                 let estr = format!("{:?}", e);
                 eprintln!("call {} error: {}", sign_name, estr);
-                if estr.contains("OOM") {
+                // Prefer the trap code wasmer attaches to the error over
+                // matching on its `Debug` text, which is an implementation
+                // detail that can (and did) drift across wasmer versions;
+                // the text match is kept only as a fallback for traps that
+                // don't carry a recognized code.
+                let is_oom = matches!(e.to_trap(), Some(wasmer::TrapCode::HeapAccessOutOfBounds)) || estr.contains("OOM");
+                if is_oom {
+                    let current_pages = self.get_memory().size().0;
+                    if current_pages >= self.limits.max_memory_pages {
+                        return ERR_CODE_MEM.to_result(format!("memory ceiling reached: wasm_uri={}, max_memory_pages={}", self.key.wasm_uri, self.limits.max_memory_pages))
+                    }
                     match self.get_memory().grow(1) {
                         Ok(p) => {
                             println!("memory grow, previous memory size: {:?}", p);
@@ -270,12 +509,35 @@ impl Instance {
                             return ERR_CODE_MEM.to_result(format!("failed to memory grow: {:?}", e))
                         }
                     }
+                } else {
+                    return ERR_CODE_NONE.to_result(estr)
                 }
+            } else if self.fuel_exhausted() {
+                return ERR_CODE_FUEL.to_result(format!("out of fuel: wasm_uri={}, sign_name={}", self.key.wasm_uri, sign_name))
+            } else if let Some(method_id) = self.perm_denied.take() {
+                return ERR_CODE_PERM.to_result(format!("handle lacks permission: wasm_uri={}, handle={}, method_id={}", self.key.wasm_uri, self.handle.id, method_id))
+            } else if let Some(token) = self.context.borrow_mut().pending.take() {
+                return Ok(CallOutcome::Yielded(token));
             } else {
-                return Ok(());
+                return Ok(CallOutcome::Done);
             }
         }
     }
+    /// Decrements remaining fuel by `units`; unmetered instances (`Limits::fuel`
+    /// left unset) always return `false`.
+    fn consume_fuel(&self, units: u64) -> bool {
+        match self.fuel_remaining.get() {
+            None => false,
+            Some(remaining) => {
+                let remaining = remaining.saturating_sub(units);
+                self.fuel_remaining.set(Some(remaining));
+                remaining == 0
+            }
+        }
+    }
+    fn fuel_exhausted(&self) -> bool {
+        matches!(self.fuel_remaining.get(), Some(0))
+    }
     fn ctx_write_to(&self, is_ctx: bool, offset: usize) {
         let mut ctx = self.context.borrow_mut();
         let cache: &mut Vec<u8> = if is_ctx {
@@ -283,7 +545,7 @@ impl Instance {
         } else {
             ctx.swap_memory.as_mut()
         };
-        self.set_view_bytes(offset as usize, cache.iter());
+        self.set_view_bytes(offset as usize, cache.as_slice());
         if !is_ctx {
             unsafe { cache.set_len(0); }
         }
@@ -302,28 +564,119 @@ impl Instance {
     fn get_view(&self) -> MemoryView<u8> {
         self.get_memory().view::<u8>()
     }
-    fn set_view_bytes<'a>(&self, offset: usize, data: impl IntoIterator<Item=&'a u8> + ExactSizeIterator) {
-        let view = self.get_view();
-        for (cell, b) in view[offset..offset + data.len()].iter().zip(data) {
-            cell.set(*b);
+    /// Bulk-copies `data` into wasm memory at `offset`. `Cell<u8>` and `u8`
+    /// share layout, so this is a single `memcpy` against the view's raw
+    /// bytes rather than a per-cell `set()` loop.
+    fn set_view_bytes(&self, offset: usize, data: &[u8]) {
+        if data.is_empty() {
+            return;
         }
+        let view = self.get_view();
+        let dst = view[offset..offset + data.len()].as_ptr() as *mut u8;
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len()) };
     }
+    /// Bulk-copies `size` bytes of wasm memory at `offset` into `buffer`,
+    /// growing `buffer` on its high-water mark like the rest of `Context`'s
+    /// scratch space. The inverse of `set_view_bytes`.
     fn read_view_bytes(&self, offset: usize, size: usize, buffer: &mut Vec<u8>) {
+        resize_with_capacity(buffer, size);
         if size == 0 {
-            resize_with_capacity(buffer, size);
             return;
         }
         let view = self.get_view();
-        for x in view[offset..(offset + size)]
-            .iter()
-            .map(|c| c.get()).enumerate() {
-            buffer[x.0] = x.1;
+        let src = view[offset..offset + size].as_ptr() as *const u8;
+        unsafe { std::ptr::copy_nonoverlapping(src, buffer.as_mut_ptr(), size) };
+    }
+    /// Dumps the whole of linear memory, for capturing a fresh
+    /// `MemorySnapshot` once this instance has run onload.
+    fn dump_memory(&self) -> Vec<u8> {
+        let len = self.get_view().len();
+        let mut buffer = Vec::new();
+        self.read_view_bytes(0, len, &mut buffer);
+        buffer
+    }
+    /// Grows this instance's memory to at least cover `snapshot`, then
+    /// copies it in wholesale. Used in place of `init()` when cloning a
+    /// warm instance onto a new thread.
+    fn restore_from_snapshot(&self, snapshot: &snapshot::MemorySnapshot) {
+        let needed_pages = snapshot.pages();
+        let current_pages = self.get_memory().size().0;
+        if needed_pages > current_pages {
+            if let Err(e) = self.get_memory().grow(needed_pages - current_pages) {
+                eprintln!("failed to grow memory to restore snapshot: wasm_uri={}, {:?}", self.key.wasm_uri, e);
+                return;
+            }
         }
+        let bytes = snapshot.bytes();
+        self.set_view_bytes(0, &bytes);
     }
+    /// Times `iterations` round trips of `set_view_bytes` + `read_view_bytes`
+    /// against `payload_size` bytes, followed by one `memory.grow(1)`. Used to
+    /// measure the win from the bulk-copy `set_view_bytes`/`read_view_bytes`
+    /// rewrite against the per-cell loop they replaced.
+    pub(crate) fn bench_memory_roundtrip(&self, iterations: u32, payload_size: usize) -> std::time::Duration {
+        let payload = vec![0u8; payload_size];
+        let mut scratch = Vec::new();
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            self.set_view_bytes(0, &payload);
+            self.read_view_bytes(0, payload_size, &mut scratch);
+        }
+        let _ = self.get_memory().grow(1);
+        start.elapsed()
+    }
+}
+
+/// Invalidates `wasm_uri`'s memory snapshot so the next local instance
+/// created for it recaptures a fresh baseline instead of cloning memory from
+/// before the reload. Call this whenever a module is reloaded out from under
+/// instances that already cloned its old snapshot -- nothing in
+/// `call_wasm_handler`/`resume_pending` does this implicitly, since a
+/// snapshot patched with whatever the last caller on any thread wrote would
+/// drift away from the clean post-`init()` baseline it's meant to be.
+pub(crate) fn reload(wasm_uri: &WasmUri) {
+    snapshot::invalidate(wasm_uri);
 }
 
 
-fn write_to_vec(msg: &dyn Message, buffer: &mut Vec<u8>) -> usize {
+/// Re-enters a parked instance's exported symbol with the saved
+/// `(ctx_size, args_size)`, delivering the outcome to `completion` (the
+/// callback `call_wasm_handler` was given when this call first yielded).
+/// Called by the `Reactor` once a parked call's wake token has been woken.
+pub(crate) fn resume_pending(key: &LocalInstanceKey, call: &PendingCall, completion: Option<Completion>) -> anyhow::Result<()> {
+    let instances = INSTANCES.read().unwrap();
+    let ins = match instances.get(key) {
+        Some(ins) => ins,
+        None => {
+            let msg = format!("instance gone: wasm_uri={}", key.wasm_uri());
+            if let Some(completion) = completion {
+                completion(ERR_CODE_NONE.to_result(msg.clone()));
+            }
+            return Err(anyhow!(msg));
+        }
+    };
+    let ins = ins.lock().unwrap();
+    *ins.pending_completion.borrow_mut() = completion;
+    match ins.invoke_instance(&call.sign_name, Some((call.ctx_size, call.args_size))) {
+        Ok(CallOutcome::Done) => {
+            let rets = ins.context.borrow_mut().out_rets();
+            if let Some(completion) = ins.pending_completion.borrow_mut().take() {
+                completion(Ok(rets));
+            }
+        }
+        // The guest yielded again; `_vm_yield` already took `pending_completion`
+        // and re-parked it under the fresh token, so there's nothing left to do.
+        Ok(CallOutcome::Yielded(_)) => {}
+        Err(e) => {
+            if let Some(completion) = ins.pending_completion.borrow_mut().take() {
+                completion(Err(e));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_to_vec(msg: &dyn Message, buffer: &mut Vec<u8>) -> usize {
     let size = msg.compute_size() as usize;
     resize_with_capacity(buffer, size);
     write_to_with_cached_sizes(msg, buffer)
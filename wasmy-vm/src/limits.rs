@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use lazy_static;
+
+use crate::WasmUri;
+
+lazy_static::lazy_static! {
+    static ref LIMITS: RwLock<HashMap<WasmUri, Limits>> = RwLock::new(HashMap::new());
+}
+
+/// Default memory ceiling: 1024 wasm pages (64 KiB each) == 64 MiB.
+const DEFAULT_MAX_MEMORY_PAGES: u32 = 1024;
+
+/// Per-`Instance` resource ceilings, set at [`crate::load`] time and carried
+/// on each local `Instance` of that module for its lifetime.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub(crate) max_memory_pages: u32,
+    pub(crate) fuel: Option<u64>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_memory_pages: DEFAULT_MAX_MEMORY_PAGES, fuel: None }
+    }
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps how many 64 KiB pages an instance's linear memory may grow to.
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u32) -> Self {
+        self.max_memory_pages = max_memory_pages;
+        self
+    }
+
+    /// Bounds the work a single `call_wasm_handler` invocation may do before
+    /// it's aborted with `ERR_CODE_FUEL`. Reset at the start of every call
+    /// (a per-call budget, not a lifetime total). Unset means unmetered.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+}
+
+/// Records the limits to apply to every local instance of `wasm_uri`.
+pub(crate) fn set(wasm_uri: WasmUri, limits: Limits) {
+    LIMITS.write().unwrap().insert(wasm_uri, limits);
+}
+
+/// Looks up the limits for `wasm_uri`, falling back to [`Limits::default`]
+/// if none were set at load time.
+pub(crate) fn get(wasm_uri: &WasmUri) -> Limits {
+    LIMITS.read().unwrap().get(wasm_uri).copied().unwrap_or_default()
+}
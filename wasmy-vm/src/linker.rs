@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use lazy_static;
+
+use crate::handler::*;
+use crate::instance::write_to_vec;
+use crate::WasmUri;
+
+lazy_static::lazy_static! {
+    static ref LINKERS: RwLock<HashMap<WasmUri, Arc<HostLinker>>> = RwLock::new(HashMap::new());
+}
+
+type RawHandler = Box<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+struct Entry {
+    namespace: String,
+    name: String,
+    handler: RawHandler,
+}
+
+/// Embedder-side registry of host functions, grouped by namespace, that a
+/// guest resolves by `(namespace, name)` instead of hard-coding a numeric
+/// method id. Functions are `define`d in whatever order the embedder likes;
+/// the position a function lands at becomes its numeric method id, resolved
+/// for the guest via `_vm_resolve`.
+#[derive(Default)]
+pub struct HostLinker {
+    entries: Vec<Entry>,
+}
+
+impl HostLinker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `f` as `namespace::name`. `Args`/`Rets` are decoded and
+    /// encoded for you; if `Args` fails to decode or `f` returns `Err`, the
+    /// caller gets back `Rets::default()` (the error is only logged).
+    pub fn define<Args, Rets, F>(&mut self, namespace: &str, name: &str, f: F) -> &mut Self
+        where Args: Message,
+              Rets: Message + Default,
+              F: Fn(Args) -> Result<Rets> + Send + Sync + 'static,
+    {
+        let namespace = namespace.to_string();
+        let name = name.to_string();
+        let ns_for_log = namespace.clone();
+        let name_for_log = name.clone();
+        let handler: RawHandler = Box::new(move |bytes: &[u8]| {
+            let rets = Args::parse_from_bytes(bytes)
+                .map_err(|e| ERR_CODE_NONE.to_code_msg(e))
+                .and_then(|args| f(args));
+            let rets = match rets {
+                Ok(rets) => rets,
+                Err(e) => {
+                    eprintln!("host function {}::{} failed: {}", ns_for_log, name_for_log, e);
+                    Rets::default()
+                }
+            };
+            let mut out = Vec::new();
+            write_to_vec(&rets, &mut out);
+            out
+        });
+        self.entries.push(Entry { namespace, name, handler });
+        self
+    }
+
+    /// Looks up the method id assigned to `namespace::name`, if any function
+    /// was `define`d under it.
+    pub(crate) fn resolve(&self, namespace: &str, name: &str) -> Option<u32> {
+        self.entries.iter().position(|e| e.namespace == namespace && e.name == name).map(|i| i as u32)
+    }
+
+    /// True if nothing was ever `define`d. `_vm_invoke` falls back to the
+    /// legacy `vm_invoke` dispatch for modules loaded this way, so existing
+    /// callers that never adopted `LoadOptions::with_linker` keep working.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Dispatches `bytes` to the function registered at `method_id`,
+    /// returning its serialized result, or an empty payload if no function
+    /// is registered at that id.
+    pub(crate) fn dispatch(&self, method_id: u32, bytes: &[u8]) -> Vec<u8> {
+        match self.entries.get(method_id as usize) {
+            Some(entry) => (entry.handler)(bytes),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Records the host function table to resolve against for every local
+/// instance of `wasm_uri`.
+pub(crate) fn set(wasm_uri: WasmUri, linker: Arc<HostLinker>) {
+    LINKERS.write().unwrap().insert(wasm_uri, linker);
+}
+
+/// Looks up the host function table for `wasm_uri`, defaulting to an empty
+/// linker if none was set at load time.
+pub(crate) fn get(wasm_uri: &WasmUri) -> Arc<HostLinker> {
+    LINKERS.read().unwrap().get(wasm_uri).cloned().unwrap_or_default()
+}
+
+impl fmt::Debug for HostLinker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.entries.iter().map(|e| format!("{}::{}", e.namespace, e.name))).finish()
+    }
+}
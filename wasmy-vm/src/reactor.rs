@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use lazy_static;
+
+use crate::handler::{OutRets, Result};
+use crate::instance;
+use crate::instance::LocalInstanceKey;
+
+lazy_static::lazy_static! {
+    static ref REACTOR: Reactor = Reactor::new();
+}
+
+static POLL_THREAD: std::sync::Once = std::sync::Once::new();
+
+/// How often the background poll thread checks for woken calls.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Invoked with the final result once a parked call completes or errors out.
+/// Handed to `Reactor::park` by `_vm_yield` so a yielding call's result still
+/// reaches its original caller instead of being discarded when resumed.
+pub(crate) type Completion = Box<dyn FnOnce(Result<OutRets>) + Send>;
+
+/// An in-flight guest invocation that parked itself via `_vm_yield` instead
+/// of running to completion. Saved so the exported symbol can be re-entered
+/// with the same arguments once the wake condition fires.
+#[derive(Clone, Debug)]
+pub(crate) struct PendingCall {
+    pub(crate) sign_name: String,
+    pub(crate) ctx_size: i32,
+    pub(crate) args_size: i32,
+}
+
+/// Handed back to the guest from `_vm_yield`; the host (or the guest itself,
+/// once its wait condition is satisfied) passes it to `_vm_wake` to mark the
+/// parked call ready to resume.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct WakeToken(pub(crate) u64);
+
+struct Parked {
+    key: LocalInstanceKey,
+    call: PendingCall,
+    woken: bool,
+    completion: Option<Completion>,
+}
+
+/// Drives guest calls that yielded rather than blocking the calling thread.
+/// `_vm_yield` parks the call under a fresh [`WakeToken`]; once something
+/// wakes that token (`_vm_wake`), `poll` re-enters the guest's exported
+/// symbol with the saved `(ctx_size, args_size)`, picking up where it left off.
+pub(crate) struct Reactor {
+    next_token: Mutex<u64>,
+    parked: RwLock<HashMap<WakeToken, Parked>>,
+}
+
+impl Reactor {
+    fn new() -> Self {
+        Reactor { next_token: Mutex::new(1), parked: RwLock::new(HashMap::new()) }
+    }
+
+    /// Returns the global `Reactor`, lazily spawning the background thread
+    /// that drives `poll()`. Without it a woken call would sit in `parked`
+    /// forever, since `wake` only flips a flag.
+    pub(crate) fn global() -> &'static Reactor {
+        POLL_THREAD.call_once(|| {
+            thread::spawn(|| loop {
+                thread::sleep(POLL_INTERVAL);
+                REACTOR.poll();
+            });
+        });
+        &REACTOR
+    }
+
+    /// Parks `call` for `key` and returns the token that will resume it.
+    /// `completion` (if any) is invoked with the eventual outcome once the
+    /// call completes or errors, however many times it resumes and yields.
+    pub(crate) fn park(&self, key: LocalInstanceKey, call: PendingCall, completion: Option<Completion>) -> WakeToken {
+        let token = {
+            let mut next_token = self.next_token.lock().unwrap();
+            let token = WakeToken(*next_token);
+            *next_token += 1;
+            token
+        };
+        #[cfg(debug_assertions)] println!("[Reactor] parked wasm_uri={}, sign_name={}, token={:?}", key.wasm_uri(), call.sign_name, token);
+        self.parked.write().unwrap().insert(token, Parked { key, call, woken: false, completion });
+        token
+    }
+
+    /// Marks `token` ready; the next `poll()` resumes its instance.
+    pub(crate) fn wake(&self, token: WakeToken) {
+        if let Some(parked) = self.parked.write().unwrap().get_mut(&token) {
+            parked.woken = true;
+        }
+    }
+
+    /// Re-enters every woken instance's exported symbol, removing it from
+    /// the park table regardless of whether it completes or yields again
+    /// under a new token.
+    pub(crate) fn poll(&self) {
+        let ready: Vec<WakeToken> = self.parked.read().unwrap()
+            .iter()
+            .filter(|(_, parked)| parked.woken)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in ready {
+            // Release the write lock before resuming: the guest may call
+            // `_vm_yield` or `_vm_wake`, which lock the park table again.
+            let parked = self.parked.write().unwrap().remove(&token);
+            if let Some(parked) = parked {
+                if let Err(e) = instance::resume_pending(&parked.key, &parked.call, parked.completion) {
+                    eprintln!("[Reactor] resume wasm_uri={} failed: {}", parked.key.wasm_uri(), e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static;
+use memmap2::MmapMut;
+
+use crate::WasmUri;
+
+const PAGE_SIZE: usize = 65536;
+
+lazy_static::lazy_static! {
+    static ref SNAPSHOTS: RwLock<HashMap<WasmUri, Arc<MemorySnapshot>>> = RwLock::new(HashMap::new());
+    static ref CLONING_ENABLED: RwLock<HashMap<WasmUri, bool>> = RwLock::new(HashMap::new());
+}
+
+/// A captured copy of an instance's linear memory, taken once the first
+/// local instance of a `wasm_uri` finishes `init()`. Backed by an mmap'd
+/// region so restoring it on a new thread is a bulk copy, not a re-run of onload.
+pub(crate) struct MemorySnapshot {
+    mmap: RwLock<MmapMut>,
+}
+
+impl MemorySnapshot {
+    fn capture(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut mmap = MmapMut::map_anon(bytes.len().max(1))?;
+        mmap[..bytes.len()].copy_from_slice(bytes);
+        Ok(MemorySnapshot { mmap: RwLock::new(mmap) })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.mmap.read().unwrap().len()
+    }
+
+    /// How many wasm pages (`memory.grow`'s unit) the snapshot covers.
+    pub(crate) fn pages(&self) -> u32 {
+        ((self.len() + PAGE_SIZE - 1) / PAGE_SIZE) as u32
+    }
+
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        self.mmap.read().unwrap().to_vec()
+    }
+
+    /// Overwrites `[start, start + bytes.len())` with `bytes`, growing the
+    /// backing mmap first if the region falls past its current length.
+    /// Nothing in this crate calls it: no dirty regions are tracked, and
+    /// `call_wasm_handler`/`resume_pending` never write back into a snapshot.
+    pub(crate) fn patch(&self, start: usize, bytes: &[u8]) -> anyhow::Result<()> {
+        let end = start + bytes.len();
+        let mut mmap = self.mmap.write().unwrap();
+        if end > mmap.len() {
+            let mut grown = MmapMut::map_anon(end)?;
+            grown[..mmap.len()].copy_from_slice(&mmap);
+            *mmap = grown;
+        }
+        mmap[start..end].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Captures `bytes` as the snapshot for `wasm_uri`, unless one already
+/// exists (only the first instance to finish `init()` should capture).
+pub(crate) fn capture_if_absent(wasm_uri: WasmUri, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut snapshots = SNAPSHOTS.write().unwrap();
+    if !snapshots.contains_key(&wasm_uri) {
+        snapshots.insert(wasm_uri, Arc::new(MemorySnapshot::capture(bytes)?));
+    }
+    Ok(())
+}
+
+/// Looks up the snapshot captured for `wasm_uri`, if any.
+pub(crate) fn get(wasm_uri: &WasmUri) -> Option<Arc<MemorySnapshot>> {
+    SNAPSHOTS.read().unwrap().get(wasm_uri).cloned()
+}
+
+/// Drops the snapshot for `wasm_uri` so the next instance created for it
+/// recaptures from scratch. Call this whenever a module is reloaded out
+/// from under its existing snapshot.
+pub(crate) fn invalidate(wasm_uri: &WasmUri) {
+    SNAPSHOTS.write().unwrap().remove(wasm_uri);
+}
+
+/// Records whether `wasm_uri`'s instances may be cloned from a shared
+/// memory snapshot instead of each thread running `init()` on its own. See
+/// `LoadOptions::with_snapshot_cloning` -- off (the default) for any module
+/// whose onload reads or stores thread-specific state.
+pub(crate) fn set_cloning_enabled(wasm_uri: WasmUri, enabled: bool) {
+    CLONING_ENABLED.write().unwrap().insert(wasm_uri, enabled);
+}
+
+/// Whether `wasm_uri` opted into snapshot cloning. Defaults to `false`.
+pub(crate) fn cloning_enabled(wasm_uri: &WasmUri) -> bool {
+    CLONING_ENABLED.read().unwrap().get(wasm_uri).copied().unwrap_or(false)
+}